@@ -5,6 +5,7 @@ use std::thread;
 use std::time::Duration;
 
 use cpu_monitor::CpuInstant;
+use cpu_monitor::history::CpuHistory;
 
 const CR_CODE: &'static str = "\x1b[G";
 const CLEAR_CODE: &'static str = "\x1b[K";
@@ -12,12 +13,19 @@ const CLEAR_CODE: &'static str = "\x1b[K";
 fn main() -> Result<(), io::Error> {
     let period = Duration::from_secs(1);
     println!("CPU monitor - time period is {:?}", period);
+    let mut history = CpuHistory::new(60);
     let mut start = CpuInstant::now()?;
     loop {
         thread::sleep(period);
         let end = CpuInstant::now()?;
         let duration = end.clone() - start;
-        print!("{}Usage: {:.0}%{}", CR_CODE, duration.non_idle() * 100., CLEAR_CODE);
+        history.push(&duration);
+        print!("{}Usage: {:>3.0}% avg {:>3.0}% {}{}",
+               CR_CODE,
+               duration.non_idle() * 100.,
+               history.average() * 100.,
+               history.sparkline(),
+               CLEAR_CODE);
         io::Write::flush(&mut io::stdout()).unwrap();
         start = end;
     }
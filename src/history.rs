@@ -0,0 +1,139 @@
+//! A small ring buffer of recent cpu usage samples, for smoothing a noisy instantaneous
+//! reading and rendering it as a compact sparkline.
+use CpuDuration;
+
+/// The glyphs used by `CpuHistory::sparkline`, from emptiest to fullest.
+const BLOCKS: [char; 9] = [' ', '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}'];
+
+/// A fixed-size, opt-in window of recent `CpuDuration` samples.
+///
+/// Keeping a short history lets callers smooth out a single noisy reading, or render the
+/// recent trend as a one-line sparkline for a terminal or status bar, without pulling in a
+/// full TUI framework.
+pub struct CpuHistory {
+    samples: Vec<f64>,
+    next: usize,
+    filled: bool,
+}
+
+impl CpuHistory {
+    /// Create a new history that keeps the last `size` samples.
+    pub fn new(size: usize) -> CpuHistory {
+        assert!(size > 0, "history size must be greater than zero");
+        CpuHistory {
+            samples: vec![0.0; size],
+            next: 0,
+            filled: false,
+        }
+    }
+
+    /// Record a new sample, overwriting the oldest one once the window is full.
+    pub fn push(&mut self, duration: &CpuDuration) {
+        self.samples[self.next] = duration.non_idle();
+        self.next = (self.next + 1) % self.samples.len();
+        if self.next == 0 {
+            self.filled = true;
+        }
+    }
+
+    /// The samples currently held, oldest first.
+    fn ordered(&self) -> Vec<f64> {
+        if self.filled {
+            self.samples[self.next..].iter()
+                .chain(self.samples[..self.next].iter())
+                .cloned()
+                .collect()
+        } else {
+            self.samples[..self.next].to_vec()
+        }
+    }
+
+    /// The moving average of `non_idle()` over the window (between 0 and 1).
+    ///
+    /// Returns `0.0` if no samples have been pushed yet.
+    pub fn average(&self) -> f64 {
+        let samples = self.ordered();
+        if samples.is_empty() {
+            return 0.0;
+        }
+        samples.iter().sum::<f64>() / samples.len() as f64
+    }
+
+    /// Render the buffered samples, oldest to newest, as a string of Unicode block
+    /// characters, one per sample.
+    pub fn sparkline(&self) -> String {
+        self.ordered().into_iter()
+            .map(|value| BLOCKS[sparkline_index(value)])
+            .collect()
+    }
+}
+
+/// Map a proportion in `0.0..=1.0` to one of the nine `BLOCKS` glyphs.
+fn sparkline_index(value: f64) -> usize {
+    let index = (value * 8.0).round();
+    if index < 0.0 {
+        0
+    } else if index > 8.0 {
+        8
+    } else {
+        index as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use proc::StatCpu;
+    use CpuDuration;
+    use super::{sparkline_index, CpuHistory};
+
+    /// Build a `CpuDuration` whose `non_idle()` is exactly `non_idle`.
+    fn duration_with_non_idle(non_idle: f64) -> CpuDuration {
+        let idle = ((1.0 - non_idle) * 1000.0) as u64;
+        let system = 1000 - idle;
+        CpuDuration {
+            duration: Duration::from_secs(1),
+            cpu_totals: StatCpu {
+                user: 0,
+                nice: 0,
+                system,
+                idle,
+                iowait: 0,
+                irq: 0,
+                softirq: 0,
+                steal: 0,
+                guest: 0,
+                guest_nice: 0,
+            },
+            cpus: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn push_wraps_and_keeps_only_the_window() {
+        let mut history = CpuHistory::new(3);
+        for &non_idle in &[0.0, 0.25, 0.5, 0.75, 1.0] {
+            history.push(&duration_with_non_idle(non_idle));
+        }
+        // the buffer only has room for 3 samples, so only the last 3 pushes (0.5, 0.75, 1.0)
+        // should remain, in the order they were pushed.
+        let average = history.average();
+        assert!((average - 0.75).abs() < 1e-9, "average was {}", average);
+        assert_eq!(history.sparkline().chars().count(), 3);
+    }
+
+    #[test]
+    fn average_of_an_empty_history_is_zero() {
+        let history = CpuHistory::new(4);
+        assert_eq!(history.average(), 0.0);
+        assert_eq!(history.sparkline(), "");
+    }
+
+    #[test]
+    fn sparkline_index_clamps_at_the_boundaries() {
+        assert_eq!(sparkline_index(0.0), 0);
+        assert_eq!(sparkline_index(1.0), 8);
+        assert_eq!(sparkline_index(-0.5), 0);
+        assert_eq!(sparkline_index(1.5), 8);
+    }
+}
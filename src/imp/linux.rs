@@ -1,10 +1,9 @@
 use std::io;
-use linux_proc::stat::Stat;
+use proc::{Stat, StatCpu};
 
-/// ans.0 is total, ans.1 is idle.
-pub fn get_cpu_totals() -> io::Result<(f64, f64)> {
+/// Returns the aggregate cpu totals, and the per-core breakdown, in the same order
+/// they appear in `/proc/stat`.
+pub fn get_cpu_totals() -> io::Result<(StatCpu, Vec<StatCpu>)> {
     let stat = Stat::from_system()?;
-    let total = stat.cpu_totals.total() as f64;
-    let idle = stat.cpu_totals.idle as f64 + stat.cpu_totals.iowait as f64;
-    Ok((total, idle))
+    Ok((stat.cpu_totals, stat.cpus))
 }
@@ -0,0 +1,48 @@
+use std::io;
+use std::mem;
+
+use winapi::shared::minwindef::FILETIME;
+use winapi::um::processthreadsapi::GetSystemTimes;
+
+use proc::StatCpu;
+
+/// Returns the aggregate cpu totals, using `GetSystemTimes`.
+///
+/// Unlike `/proc/stat` on Linux, this API doesn't expose a per-core breakdown, so the
+/// per-core list is always empty.
+pub fn get_cpu_totals() -> io::Result<(StatCpu, Vec<StatCpu>)> {
+    let mut idle_time: FILETIME = unsafe { mem::zeroed() };
+    let mut kernel_time: FILETIME = unsafe { mem::zeroed() };
+    let mut user_time: FILETIME = unsafe { mem::zeroed() };
+
+    let ok = unsafe { GetSystemTimes(&mut idle_time, &mut kernel_time, &mut user_time) };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let idle = filetime_to_u64(idle_time);
+    let kernel = filetime_to_u64(kernel_time);
+    let user = filetime_to_u64(user_time);
+    // `kernel` already includes `idle`, so the time spent doing actual kernel work is the
+    // remainder.
+    let system = kernel.checked_sub(idle).unwrap();
+
+    let cpu_totals = StatCpu {
+        user,
+        nice: 0,
+        system,
+        idle,
+        iowait: 0,
+        irq: 0,
+        softirq: 0,
+        steal: 0,
+        guest: 0,
+        guest_nice: 0,
+    };
+    Ok((cpu_totals, Vec::new()))
+}
+
+/// Combine a `FILETIME`'s two 32-bit halves into the 64-bit count of 100-ns ticks it represents.
+fn filetime_to_u64(ft: FILETIME) -> u64 {
+    ((ft.dwHighDateTime as u64) << 32) | ft.dwLowDateTime as u64
+}
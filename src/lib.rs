@@ -1,21 +1,29 @@
 
 #[cfg(windows)]
 extern crate winapi;
-#[cfg(unix)]
-extern crate linux_proc;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
 
 use std::time;
 use std::ops;
 use std::io;
 
 mod imp;
+pub mod history;
+pub mod monitor;
+pub mod proc;
+
+use proc::StatCpu;
 
 /// Like `std::time::Instant`, but with information about the cpu usage stats.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct CpuInstant {
     instant: time::Instant,
-    cpu_total: f64,
-    cpu_idle: f64,
+    cpu_totals: StatCpu,
+    cpus: Vec<StatCpu>,
 }
 
 impl CpuInstant {
@@ -23,11 +31,11 @@ impl CpuInstant {
     ///
     /// The main constructor method of the crate.
     pub fn now() -> io::Result<CpuInstant> {
-        let (cpu_total, cpu_idle) = imp::get_cpu_totals()?;
+        let (cpu_totals, cpus) = imp::get_cpu_totals()?;
         Ok(CpuInstant {
             instant: time::Instant::now(),
-            cpu_total,
-            cpu_idle,
+            cpu_totals,
+            cpus,
         })
     }
 
@@ -41,10 +49,15 @@ impl ops::Sub for CpuInstant {
     type Output = CpuDuration;
 
     fn sub(self, rhs: Self) -> Self::Output {
+        assert_eq!(self.cpus.len(), rhs.cpus.len(), "different number of cpus");
+        let cpus = self.cpus.into_iter()
+            .zip(rhs.cpus.into_iter())
+            .map(|(this, rhs)| this - rhs)
+            .collect();
         CpuDuration {
             duration: self.instant - rhs.instant,
-            cpu_total: self.cpu_total - rhs.cpu_total,
-            cpu_idle: self.cpu_idle - rhs.cpu_idle,
+            cpu_totals: self.cpu_totals - rhs.cpu_totals,
+            cpus,
         }
     }
 }
@@ -52,11 +65,12 @@ impl ops::Sub for CpuInstant {
 /// Like `std::time::Duration`, but with information about the cpu usage stats.
 ///
 /// The way to get this is to subtract one `CpuInstant` from another.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CpuDuration {
     duration: time::Duration,
-    cpu_total: f64,
-    cpu_idle: f64,
+    cpu_totals: StatCpu,
+    cpus: Vec<StatCpu>,
 }
 
 impl CpuDuration {
@@ -66,13 +80,105 @@ impl CpuDuration {
     }
 
     /// The proportion of the time spent idle (between 0 and 1).
+    ///
+    /// This does not include time spent waiting for I/O; see `iowait()` for that.
     pub fn idle(&self) -> f64 {
-        self.cpu_idle / self.cpu_total
+        self.cpu_totals.idle as f64 / self.cpu_totals.total() as f64
     }
 
     /// The proportion of the time spent not idle (between 0 and 1).
     pub fn non_idle(&self) -> f64 {
         1.0 - self.idle()
     }
+
+    /// The proportion of time spent not idle, for each logical cpu core, in the same order
+    /// they appear in `/proc/stat`.
+    pub fn non_idle_per_cpu(&self) -> Vec<f64> {
+        self.cpus.iter()
+            .map(|cpu| 1.0 - cpu.idle as f64 / cpu.total() as f64)
+            .collect()
+    }
+
+    /// The proportion of time spent running non-niced user processes.
+    ///
+    /// The kernel already folds `guest()` time into this figure, so summing `user()` and
+    /// `guest()` double-counts it; use `user() - guest()` for the non-guest share.
+    pub fn user(&self) -> f64 {
+        self.cpu_totals.user as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent running niced user processes.
+    pub fn nice(&self) -> f64 {
+        self.cpu_totals.nice as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent running kernel processes.
+    pub fn system(&self) -> f64 {
+        self.cpu_totals.system as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent waiting for I/O to complete.
+    pub fn iowait(&self) -> f64 {
+        self.cpu_totals.iowait as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent servicing interrupts.
+    pub fn irq(&self) -> f64 {
+        self.cpu_totals.irq as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent servicing softirqs.
+    pub fn softirq(&self) -> f64 {
+        self.cpu_totals.softirq as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time stolen by other operating systems running in a virtualised
+    /// environment.
+    pub fn steal(&self) -> f64 {
+        self.cpu_totals.steal as f64 / self.cpu_totals.total() as f64
+    }
+
+    /// The proportion of time spent running virtual CPUs for guest operating systems.
+    ///
+    /// This time is already included in `user()` (the kernel counts it there too), so it's
+    /// a breakdown of `user()`, not an additional category on top of it.
+    pub fn guest(&self) -> f64 {
+        self.cpu_totals.guest as f64 / self.cpu_totals.total() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time;
+    use proc::StatCpu;
+    use CpuDuration;
+
+    #[test]
+    fn cpu_duration_proportions() {
+        // total (excluding guest/guest_nice) = 20 + 50 + 20 + 10 = 100
+        let duration = CpuDuration {
+            duration: time::Duration::from_secs(1),
+            cpu_totals: StatCpu {
+                user: 50,
+                nice: 0,
+                system: 20,
+                idle: 20,
+                iowait: 10,
+                irq: 0,
+                softirq: 0,
+                steal: 0,
+                guest: 15,
+                guest_nice: 0,
+            },
+            cpus: Vec::new(),
+        };
+
+        assert_eq!(duration.idle(), 0.20);
+        assert_eq!(duration.iowait(), 0.10);
+        assert_eq!(duration.user(), 0.50);
+        assert_eq!(duration.system(), 0.20);
+        assert_eq!(duration.guest(), 0.15);
+        assert!((duration.non_idle() - 0.80).abs() < 1e-9);
+    }
 }
 
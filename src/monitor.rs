@@ -0,0 +1,112 @@
+//! A background sampling service, for embedding continuous cpu telemetry in daemons and
+//! agents that don't want to manage their own sampling loop.
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use {CpuDuration, CpuInstant};
+
+/// Samples cpu usage on a background thread at a fixed interval, and publishes the latest
+/// `CpuDuration` for consumers to read without blocking or managing their own timing loop.
+pub struct CpuMonitor {
+    latest: Arc<Mutex<Option<CpuDuration>>>,
+    subscribers: Arc<Mutex<Vec<Sender<CpuDuration>>>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CpuMonitor {
+    /// Start sampling in the background, once every `interval`.
+    pub fn new(interval: Duration) -> io::Result<CpuMonitor> {
+        let mut start = CpuInstant::now()?;
+        let latest = Arc::new(Mutex::new(None));
+        let subscribers: Arc<Mutex<Vec<Sender<CpuDuration>>>> = Arc::new(Mutex::new(Vec::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        // The longest we ever sleep in one go while waiting out `interval`, so `stop()` can
+        // notice the shutdown flag and return promptly even when `interval` is long.
+        let slice = Duration::from_millis(100);
+
+        let thread_latest = Arc::clone(&latest);
+        let thread_subscribers = Arc::clone(&subscribers);
+        let thread_shutdown = Arc::clone(&shutdown);
+        let handle = thread::spawn(move || {
+            while !thread_shutdown.load(Ordering::Relaxed) {
+                let mut remaining = interval;
+                while remaining > Duration::from_secs(0) {
+                    if thread_shutdown.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let nap = if remaining < slice { remaining } else { slice };
+                    thread::sleep(nap);
+                    remaining -= nap;
+                }
+
+                let end = match CpuInstant::now() {
+                    Ok(end) => end,
+                    Err(_) => continue,
+                };
+                let duration = end.clone() - start;
+                start = end;
+
+                *thread_latest.lock().unwrap() = Some(duration.clone());
+                thread_subscribers.lock().unwrap()
+                    .retain(|tx| tx.send(duration.clone()).is_ok());
+            }
+        });
+
+        Ok(CpuMonitor {
+            latest,
+            subscribers,
+            shutdown,
+            handle: Some(handle),
+        })
+    }
+
+    /// The most recently published sample, or `None` if a sample hasn't landed yet.
+    pub fn latest(&self) -> Option<CpuDuration> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    /// Subscribe to be sent every new sample as it's published.
+    pub fn subscribe(&self) -> Receiver<CpuDuration> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Stop the background thread, blocking until it has shut down.
+    ///
+    /// Called automatically on `Drop`.
+    pub fn stop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for CpuMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use super::CpuMonitor;
+
+    #[test]
+    fn stop_returns_promptly_even_with_a_long_interval() {
+        let mut monitor = CpuMonitor::new(Duration::from_secs(10)).unwrap();
+
+        let before = Instant::now();
+        monitor.stop();
+        assert!(before.elapsed() < Duration::from_secs(1),
+                "stop() took {:?}, expected it to return promptly", before.elapsed());
+    }
+}
@@ -1,14 +1,26 @@
 //! Parsers for the contents of the `/proc` directory.
 //!
 use nom;
+use std::collections::HashMap;
 use std::io::{self, BufReader, BufRead};
 use std::fs::File;
 use std::time::Duration;
 use std::ops;
 use std;
 
+/// Parse one of the types in this module from any buffered reader.
+///
+/// This mirrors the `FromRead`/`FromBufRead` pattern used by the `procfs` crate, and makes
+/// the parsers usable against captured fixtures or other non-file sources, not just the
+/// real `/proc` files.
+pub trait FromRead: Sized {
+    /// Parse `Self` from a buffered reader.
+    fn from_read<R: BufRead>(reader: R) -> io::Result<Self>;
+}
+
 /// The stats from `/proc/stat`.
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Stat {
     /// Total stats, sum of all cpus.
     pub cpu_totals: StatCpu,
@@ -92,9 +104,16 @@ impl<'a> ops::Sub<&'a Stat> for Stat {
         }
     }
 }
+impl FromRead for Stat {
+    fn from_read<R: BufRead>(reader: R) -> io::Result<Stat> {
+        Stat::from_iter(reader.lines())
+    }
+}
+
 impl Stat {
+    /// Parse the stats from the real `/proc/stat` file.
     pub fn from_system() -> io::Result<Self> {
-        Stat::from_iter(BufReader::new(File::open("/proc/stat")?).lines())
+        Stat::from_read(BufReader::new(File::open("/proc/stat")?))
     }
 
     fn from_iter(mut iter: impl Iterator<Item=io::Result<String>>) -> io::Result<Stat> {
@@ -131,6 +150,7 @@ impl Stat {
 
 /// The change in the stat values over a time period
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StatDelta {
     /// The number of *units* over the time period.
     pub cpu_totals: StatCpu,
@@ -163,6 +183,7 @@ where I: Iterator<Item=io::Result<String>>,
 /// *units* could be anything, for example cpu cycles, or hundredths of a second. The numbers only
 /// really make sense as a proportion of the total.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct StatCpu {
     pub user: u64,
     pub nice: u64,
@@ -173,6 +194,7 @@ pub struct StatCpu {
     pub softirq: u64,
     pub steal: u64,
     pub guest: u64,
+    pub guest_nice: u64,
 }
 
 impl ops::Sub for StatCpu {
@@ -189,6 +211,7 @@ impl ops::Sub for StatCpu {
             softirq: self.softirq.checked_sub(rhs.softirq).unwrap(),
             steal: self.steal.checked_sub(rhs.steal).unwrap(),
             guest: self.guest.checked_sub(rhs.guest).unwrap(),
+            guest_nice: self.guest_nice.checked_sub(rhs.guest_nice).unwrap(),
         }
     }
 }
@@ -198,6 +221,10 @@ impl StatCpu {
         parse_cpu_line(input).map(|(_, answer)| answer).ok()
     }
 
+    /// The total number of *units* over all contexts.
+    ///
+    /// `guest` and `guest_nice` are excluded: the kernel already counts guest time inside
+    /// `user`, and guest_nice time inside `nice`, so adding them again would double-count it.
     pub fn total(&self) -> u64 {
         self.user
             .checked_add(self.nice).unwrap()
@@ -207,7 +234,6 @@ impl StatCpu {
             .checked_add(self.irq).unwrap()
             .checked_add(self.softirq).unwrap()
             .checked_add(self.steal).unwrap()
-            .checked_add(self.guest).unwrap()
     }
 
 }
@@ -233,7 +259,9 @@ named!(parse_cpu_line<&str, StatCpu>, do_parse!(
     steal: call!(parse_u64) >>
     call!(nom::space0) >>
     guest: call!(parse_u64) >>
-    (StatCpu { user, nice, system, idle, iowait, irq, softirq, steal, guest })
+    call!(nom::space0) >>
+    guest_nice: call!(parse_u64) >>
+    (StatCpu { user, nice, system, idle, iowait, irq, softirq, steal, guest, guest_nice })
 ));
 
 
@@ -274,12 +302,24 @@ pub struct DiskStats {
     inner: Vec<DiskStat>
 }
 
+impl FromRead for DiskStats {
+    fn from_read<R: BufRead>(reader: R) -> io::Result<DiskStats> {
+        let mut inner = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            inner.push(DiskStat::from_str(&line)?);
+        }
+        Ok(DiskStats { inner })
+    }
+}
+
 impl DiskStats {
+    /// Parse the stats from the real `/proc/diskstats` file.
     pub fn from_system() -> io::Result<Self> {
-        let mut reader = BufReader::new(File::open("/proc/diskstats")?);
-        let mut disk_stats = Vec::new();
-
-        unimplemented!()
+        DiskStats::from_read(BufReader::new(File::open("/proc/diskstats")?))
     }
 
     pub fn iter(&self) -> impl Iterator<Item=&DiskStat> {
@@ -295,6 +335,31 @@ impl IntoIterator for DiskStats {
     }
 }
 
+impl ops::Sub for DiskStats {
+    type Output = Vec<DiskStatDelta>;
+
+    /// Pair up devices by `(major, minor)` and produce a delta for each one present in both
+    /// samples.
+    ///
+    /// Devices that only appear in one of the two samples (a disk hot-plugged or removed
+    /// between them) are skipped, rather than naively zipping the two lists in whatever
+    /// order `/proc/diskstats` happened to report them.
+    fn sub(self, rhs: Self) -> Self::Output {
+        let mut rhs_by_device: HashMap<(u64, u64), DiskStat> = rhs.inner.into_iter()
+            .map(|stat| ((stat.major, stat.minor), stat))
+            .collect();
+        self.inner.into_iter()
+            .filter_map(|stat| {
+                rhs_by_device.remove(&(stat.major, stat.minor))
+                    .map(|rhs_stat| stat - rhs_stat)
+            })
+            .collect()
+    }
+}
+
+/// The stats for a single block device, as reported in one line of `/proc/diskstats`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DiskStat {
     pub major: u64,
     pub minor: u64,
@@ -316,10 +381,87 @@ pub struct DiskStat {
     pub time_io_weighted: Duration,
 }
 
+impl DiskStat {
+    /// Parse one line of `/proc/diskstats`.
+    ///
+    /// Only the first 11 counters (present since the field was introduced) are required;
+    /// extra fields added by newer kernels (discard and flush counters) are accepted and
+    /// ignored without being parsed.
+    fn from_str(input: &str) -> io::Result<DiskStat> {
+        let fields: Vec<&str> = input.split_whitespace().collect();
+        if fields.len() < 14 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let parse = |s: &str| s.parse::<u64>().map_err(|_| io::Error::from(io::ErrorKind::InvalidData));
+        Ok(DiskStat {
+            major: parse(fields[0])?,
+            minor: parse(fields[1])?,
+            name: fields[2].to_string(),
+            reads_completed: parse(fields[3])?,
+            reads_merged: parse(fields[4])?,
+            sectors_read: parse(fields[5])?,
+            time_reading: Duration::from_millis(parse(fields[6])?),
+            writes_completed: parse(fields[7])?,
+            writes_merged: parse(fields[8])?,
+            sectors_written: parse(fields[9])?,
+            time_writing: Duration::from_millis(parse(fields[10])?),
+            io_in_progress: parse(fields[11])?,
+            time_io: Duration::from_millis(parse(fields[12])?),
+            time_io_weighted: Duration::from_millis(parse(fields[13])?),
+        })
+    }
+}
+
+/// The change in a device's stats over a time period.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DiskStatDelta {
+    pub major: u64,
+    pub minor: u64,
+    pub name: String,
+    pub reads_completed: u64,
+    pub reads_merged: u64,
+    pub sectors_read: u64,
+    pub time_reading: Duration,
+    pub writes_completed: u64,
+    pub writes_merged: u64,
+    pub sectors_written: u64,
+    pub time_writing: Duration,
+    /// The number of I/Os in progress. Not cumulative, so this is the later of the two
+    /// readings rather than a difference.
+    pub io_in_progress: u64,
+    pub time_io: Duration,
+    pub time_io_weighted: Duration,
+}
+
+impl ops::Sub for DiskStat {
+    type Output = DiskStatDelta;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        assert_eq!((self.major, self.minor), (rhs.major, rhs.minor), "different devices");
+        DiskStatDelta {
+            major: self.major,
+            minor: self.minor,
+            name: self.name,
+            reads_completed: self.reads_completed.checked_sub(rhs.reads_completed).unwrap(),
+            reads_merged: self.reads_merged.checked_sub(rhs.reads_merged).unwrap(),
+            sectors_read: self.sectors_read.checked_sub(rhs.sectors_read).unwrap(),
+            time_reading: self.time_reading.checked_sub(rhs.time_reading).unwrap(),
+            writes_completed: self.writes_completed.checked_sub(rhs.writes_completed).unwrap(),
+            writes_merged: self.writes_merged.checked_sub(rhs.writes_merged).unwrap(),
+            sectors_written: self.sectors_written.checked_sub(rhs.sectors_written).unwrap(),
+            time_writing: self.time_writing.checked_sub(rhs.time_writing).unwrap(),
+            io_in_progress: self.io_in_progress,
+            time_io: self.time_io.checked_sub(rhs.time_io).unwrap(),
+            time_io_weighted: self.time_io_weighted.checked_sub(rhs.time_io_weighted).unwrap(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::io::{self, BufRead};
-    use super::Stat;
+    use std::io;
+    use std::time::Duration;
+    use super::{DiskStats, FromRead, Stat, StatCpu};
 
     #[test]
     fn parse_single() {
@@ -336,6 +478,25 @@ mod tests {
                    Result::Ok(("", 12)));
     }
 
+    #[test]
+    fn stat_cpu_total_excludes_guest() {
+        let cpu = StatCpu {
+            user: 50,
+            nice: 5,
+            system: 20,
+            idle: 20,
+            iowait: 10,
+            irq: 1,
+            softirq: 1,
+            steal: 0,
+            guest: 15,
+            guest_nice: 2,
+        };
+        // guest/guest_nice are already folded into user/nice by the kernel, so they must
+        // not be added again.
+        assert_eq!(cpu.total(), 50 + 5 + 20 + 20 + 10 + 1 + 1);
+    }
+
     #[test]
     fn proc_stat() {
         let raw = "\
@@ -352,6 +513,60 @@ procs_running 1
 procs_blocked 0
 softirq 4257581 64 299604 69 2986 36581 0 3497229 283111 0 137937
 ";
-        let _stat = Stat::from_iter(io::Cursor::new(raw).lines()).unwrap();
+        let _stat = Stat::from_read(io::Cursor::new(raw)).unwrap();
+    }
+
+    #[test]
+    fn proc_diskstats() {
+        let raw = "\
+   8       0 sda 108375 2993 8496976 37060 217132 165441 15344128 321646 0 68380 358706
+   8       1 sda1 108156 2993 8491952 37016 214241 165441 15344128 321546 0 68316 358562
+ 259       0 nvme0n1 451216 0 26896192 95312 337520 12 8208 62340 0 128860 157652 0 0 0 0 0 0
+";
+        let disk_stats = DiskStats::from_read(io::Cursor::new(raw)).unwrap();
+        let disks: Vec<_> = disk_stats.iter().collect();
+        assert_eq!(disks.len(), 3);
+
+        let sda = &disks[0];
+        assert_eq!(sda.major, 8);
+        assert_eq!(sda.minor, 0);
+        assert_eq!(sda.name, "sda");
+        assert_eq!(sda.reads_completed, 108375);
+        assert_eq!(sda.time_reading, Duration::from_millis(37060));
+        assert_eq!(sda.writes_completed, 217132);
+        assert_eq!(sda.time_writing, Duration::from_millis(321646));
+        assert_eq!(sda.io_in_progress, 0);
+        assert_eq!(sda.time_io, Duration::from_millis(68380));
+        assert_eq!(sda.time_io_weighted, Duration::from_millis(358706));
+
+        // nvme0n1 has extra discard/flush counters beyond the 11 documented fields; they
+        // should be accepted and ignored rather than rejected.
+        let nvme = &disks[2];
+        assert_eq!(nvme.name, "nvme0n1");
+        assert_eq!(nvme.reads_completed, 451216);
+        assert_eq!(nvme.time_io_weighted, Duration::from_millis(157652));
+    }
+
+    #[test]
+    fn diskstats_sub_pairs_by_device() {
+        let before = "\
+   8       0 sda 100 0 1000 10 50 0 500 20 0 30 30
+ 259       0 nvme0n1 10 0 100 1 5 0 50 2 0 3 3
+";
+        let after = "\
+   8       0 sda 150 0 1500 15 80 0 800 30 0 45 45
+ 259       1 nvme0n1p1 1 0 10 1 1 0 10 1 0 2 2
+";
+        let before = DiskStats::from_read(io::Cursor::new(before)).unwrap();
+        let after = DiskStats::from_read(io::Cursor::new(after)).unwrap();
+
+        // sda (8, 0) appears in both samples and should produce a delta; nvme0n1 (259, 0)
+        // only appears in `before`, and nvme0n1p1 (259, 1) only in `after` -- neither should
+        // be naively paired with an unrelated device.
+        let deltas = after - before;
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].major, 8);
+        assert_eq!(deltas[0].minor, 0);
+        assert_eq!(deltas[0].reads_completed, 50);
     }
 }